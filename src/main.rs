@@ -11,7 +11,7 @@ struct Registers {
     c: u8,
     d: u8,
     e: u8,
-    f: FlagsRegister, 
+    f: FlagsRegister,
     h: u8,
     l: u8,
     sp: u16,
@@ -28,7 +28,7 @@ impl Registers{
 
     fn set_bc(&mut self, value: u16){
         self.b = ((value & 0xFF00) >> 8) as u8;
-        self.c = (value & 0xFF) as u8; 
+        self.c = (value & 0xFF) as u8;
     }
 
     fn get_af(&self) -> u16 {
@@ -38,7 +38,7 @@ impl Registers{
 
     fn set_af(&mut self, value: u16){
         self.a = ((value & 0xFF00) >> 8) as u8;
-        self.f = FlagsRegister::from((value & 0xFF) as u8); 
+        self.f = FlagsRegister::from((value & 0xFF) as u8);
     }
 
     fn get_de(&self) -> u16 {
@@ -48,7 +48,7 @@ impl Registers{
 
     fn set_de(&mut self, value: u16){
         self.d = ((value & 0xFF00) >> 8) as u8;
-        self.e = (value & 0xFF) as u8; 
+        self.e = (value & 0xFF) as u8;
     }
 
     fn get_hl(&self) -> u16 {
@@ -58,7 +58,7 @@ impl Registers{
 
     fn set_hl(&mut self, value: u16){
         self.h = ((value & 0xFF00) >> 8) as u8;
-        self.l = (value & 0xFF) as u8; 
+        self.l = (value & 0xFF) as u8;
     }
 }
 
@@ -76,20 +76,20 @@ impl std::convert::From<&FlagsRegister> for u8 {
        (if flag.zero { 1 } else { 0 })          << ZERO_FLAG_BYTE_POSITION |
        (if flag.subtract { 1 } else { 0 })      << SUBTRACT_FLAG_BYTE_POSITION |
        (if flag.half_carry { 1 } else { 0 })    << HALF_CARRY_FLAG_BYTE_POSITION |
-       (if flag.carry { 1 } else { 0 })         << CARRY_FLAG_BYTE_POSITION 
+       (if flag.carry { 1 } else { 0 })         << CARRY_FLAG_BYTE_POSITION
     }
 }
 
 impl std::convert::From<u8> for FlagsRegister {
     fn from(byte: u8) -> Self {
 
-        let zero = (byte & 0xFF) >> ZERO_FLAG_BYTE_POSITION != 0;
-        let subtract = (byte & 0xFF) >> SUBTRACT_FLAG_BYTE_POSITION != 0;
-        let half_carry = (byte & 0xFF) >> HALF_CARRY_FLAG_BYTE_POSITION != 01;
-        let carry = (byte & 0xFF) >> CARRY_FLAG_BYTE_POSITION != 0;
+        let zero = (byte >> ZERO_FLAG_BYTE_POSITION) & 0b1 != 0;
+        let subtract = (byte >> SUBTRACT_FLAG_BYTE_POSITION) & 0b1 != 0;
+        let half_carry = (byte >> HALF_CARRY_FLAG_BYTE_POSITION) & 0b1 != 0;
+        let carry = (byte >> CARRY_FLAG_BYTE_POSITION) & 0b1 != 0;
 
         FlagsRegister{
-            zero, 
+            zero,
             subtract,
             half_carry,
             carry
@@ -97,19 +97,370 @@ impl std::convert::From<u8> for FlagsRegister {
     }
 }
 
-struct CPU {
+//selects the behavioral differences between chip revisions: post-boot register
+//values and whether double-speed mode and the CGB-only banking registers are live.
+//The CPU is monomorphized per model through this trait; the memory bus is not
+//generic and receives `IS_CGB` as a plain bool at construction, which it checks at
+//runtime for the CGB-only registers (0xFF4F, 0xFF70, KEY1).
+trait Model {
+    const IS_CGB: bool;
+
+    fn initial_registers() -> Registers;
+}
+
+//original DMG-01
+struct Dmg;
+
+//Game Boy Color, running in CGB mode
+struct Cgb;
+
+impl Model for Dmg {
+    const IS_CGB: bool = false;
+
+    fn initial_registers() -> Registers {
+        Registers {
+            a: 0x01,
+            b: 0x00,
+            c: 0x13,
+            d: 0x00,
+            e: 0xD8,
+            f: FlagsRegister::from(0xB0),
+            h: 0x01,
+            l: 0x4D,
+            sp: 0xFFFE,
+        }
+    }
+}
+
+impl Model for Cgb {
+    const IS_CGB: bool = true;
+
+    fn initial_registers() -> Registers {
+        Registers {
+            a: 0x11,
+            b: 0x00,
+            c: 0x00,
+            d: 0xFF,
+            e: 0x56,
+            f: FlagsRegister::from(0x80),
+            h: 0x00,
+            l: 0x0D,
+            sp: 0xFFFE,
+        }
+    }
+}
+
+struct CPU<M: Model> {
     registers: Registers,
     pc: u16,
-    bus: MemoryBus
+    bus: MemoryBus,
+    //running count of machine (T-state) cycles executed, for synchronizing the PPU,
+    //timer, and APU to the CPU
+    clock: u64,
+    //interrupt master enable - gates whether handle_interrupts will dispatch at all
+    ime: bool,
+    //EI takes effect after the *next* instruction, not immediately
+    ei_pending: bool,
+    //HALT suspends stepping until an interrupt is pending
+    halted: bool,
+    //STOP suspends stepping the same way (and on hardware also stops most clocks)
+    stopped: bool,
+    //PC addresses that `run` should pause in front of, for a debugger front-end
+    breakpoints: Vec<u16>,
+    //CGB KEY1 double-speed mode; flipped by executing STOP while a speed switch is
+    //armed via KEY1 (0xFF4D) bit 0. Halves the cycle count `step` reports so
+    //timing-dependent peripherals still see real time elapse, not CPU time.
+    double_speed: bool,
+    model: std::marker::PhantomData<M>,
 }
 
+//priority order: VBlank, LCD STAT, Timer, Serial, Joypad
+const INTERRUPT_VECTORS: [u16; 5] = [0x40, 0x48, 0x50, 0x58, 0x60];
+
+//the Game Boy address space: a fixed ROM bank, a switchable (mapper-controlled) ROM
+//bank, VRAM, switchable external cartridge RAM, WRAM, OAM, I/O registers, and HRAM
 struct MemoryBus {
-    memory: [u8; 0xFFFF]
+    cartridge: Cartridge,
+    //CGB has 2 VRAM banks (switched via 0xFF4F) and 8 WRAM banks (bank 0 fixed at
+    //0xC000-0xCFFF, 1-7 switched into 0xD000-0xDFFF via 0xFF70); on DMG only VRAM
+    //bank 0 and WRAM banks 0 and 1 are ever selected
+    vram: [[u8; 0x2000]; 2],  //0x8000-0x9FFF
+    wram: [[u8; 0x1000]; 8],  //0xC000-0xDFFF (0xE000-0xFDFF echoes it)
+    oam: [u8; 0xA0],          //0xFE00-0xFE9F
+    io: [u8; 0x80],           //0xFF00-0xFF7F
+    hram: [u8; 0x7F],         //0xFF80-0xFFFE
+    interrupt_enable: u8,     //0xFFFF
+    cgb: bool,
+    vram_bank: u8,
+    wram_bank: u8,
 }
-  
+
 impl MemoryBus {
+    //parse the cartridge header to pick a mapper and size the external RAM, then
+    //build a bus around it. `cgb` selects whether the CGB-only bank-switch
+    //registers (0xFF4F, 0xFF70) actually switch banks.
+    fn load_rom(rom: &[u8], cgb: bool) -> MemoryBus {
+        MemoryBus {
+            cartridge: Cartridge::from_rom(rom),
+            vram: [[0; 0x2000]; 2],
+            wram: [[0; 0x1000]; 8],
+            oam: [0; 0xA0],
+            io: [0; 0x80],
+            hram: [0; 0x7F],
+            interrupt_enable: 0,
+            cgb,
+            vram_bank: 0,
+            wram_bank: 0,
+        }
+    }
+
+    //bank 0 is already fixed at 0xC000-0xCFFF, so selecting it again for
+    //0xD000-0xDFFF isn't possible; SVBK=0 is treated as bank 1, same as the console does
+    fn effective_wram_bank(&self) -> usize {
+        match self.wram_bank & 0x7 {
+            0 => 1,
+            bank => bank as usize,
+        }
+    }
+
     fn read_byte(&self, address: u16) -> u8 {
-        self.memory[address as usize]
+        match address {
+            0x0000..=0x7FFF => self.cartridge.read_rom(address),
+            0x8000..=0x9FFF => self.vram[self.vram_bank as usize][address as usize - 0x8000],
+            0xA000..=0xBFFF => self.cartridge.read_ram(address - 0xA000),
+            0xC000..=0xCFFF => self.wram[0][address as usize - 0xC000],
+            0xD000..=0xDFFF => self.wram[self.effective_wram_bank()][address as usize - 0xD000],
+            0xE000..=0xEFFF => self.wram[0][address as usize - 0xE000],
+            0xF000..=0xFDFF => self.wram[self.effective_wram_bank()][address as usize - 0xF000],
+            0xFE00..=0xFE9F => self.oam[address as usize - 0xFE00],
+            0xFEA0..=0xFEFF => 0xFF, //unusable
+            0xFF00..=0xFF7F => self.io[address as usize - 0xFF00],
+            0xFF80..=0xFFFE => self.hram[address as usize - 0xFF80],
+            0xFFFF => self.interrupt_enable,
+        }
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        match address {
+            //writes into ROM space are intercepted as MBC control registers, not
+            //stored anywhere
+            0x0000..=0x7FFF => self.cartridge.write_control(address, value),
+            0x8000..=0x9FFF => self.vram[self.vram_bank as usize][address as usize - 0x8000] = value,
+            0xA000..=0xBFFF => self.cartridge.write_ram(address - 0xA000, value),
+            0xC000..=0xCFFF => self.wram[0][address as usize - 0xC000] = value,
+            0xD000..=0xDFFF => {
+                let bank = self.effective_wram_bank();
+                self.wram[bank][address as usize - 0xD000] = value;
+            }
+            0xE000..=0xEFFF => self.wram[0][address as usize - 0xE000] = value,
+            0xF000..=0xFDFF => {
+                let bank = self.effective_wram_bank();
+                self.wram[bank][address as usize - 0xF000] = value;
+            }
+            0xFE00..=0xFE9F => self.oam[address as usize - 0xFE00] = value,
+            0xFEA0..=0xFEFF => {} //unusable
+            //CGB-only VRAM/WRAM bank-switch registers; on DMG the write is stored
+            //(so it reads back) but doesn't move any banks
+            0xFF4F => {
+                self.io[0x4F] = value;
+                if self.cgb {
+                    self.vram_bank = value & 0x1;
+                }
+            }
+            0xFF70 => {
+                self.io[0x70] = value;
+                if self.cgb {
+                    self.wram_bank = value & 0x7;
+                }
+            }
+            //CGB KEY1: bit 0 arms a pending speed switch (consumed by a STOP while
+            //armed, see `speed_switch_armed`/`complete_speed_switch`); bit 7 (current
+            //speed) is read-only from software and only flipped by completing a switch
+            0xFF4D => {
+                if self.cgb {
+                    self.io[0x4D] = (self.io[0x4D] & 0x80) | (value & 0x01);
+                } else {
+                    self.io[0x4D] = value;
+                }
+            }
+            0xFF00..=0xFF7F => self.io[address as usize - 0xFF00] = value,
+            0xFF80..=0xFFFE => self.hram[address as usize - 0xFF80] = value,
+            0xFFFF => self.interrupt_enable = value,
+        }
+    }
+
+    //battery-backed save data, for cartridges that have a battery to retain it
+    fn battery_ram(&self) -> Option<&[u8]> {
+        self.cartridge.battery_ram()
+    }
+
+    fn load_battery_ram(&mut self, data: &[u8]) {
+        self.cartridge.load_battery_ram(data);
+    }
+
+    //IF (0xFF0F): which interrupts are currently requested
+    fn interrupt_flag(&self) -> u8 {
+        self.io[0x0F]
+    }
+
+    //KEY1 (0xFF4D) bit 0: true once software has armed a speed switch for the
+    //next STOP to consume
+    fn speed_switch_armed(&self) -> bool {
+        self.cgb && self.io[0x4D] & 0x01 != 0
+    }
+
+    //toggles KEY1 bit 7 (current speed) and clears the arm bit; returns the new
+    //double-speed state for the CPU to adopt
+    fn complete_speed_switch(&mut self) -> bool {
+        let new_speed = self.io[0x4D] & 0x80 == 0;
+        self.io[0x4D] = if new_speed { 0x80 } else { 0x00 };
+        new_speed
+    }
+
+    fn set_interrupt_flag(&mut self, value: u8) {
+        self.io[0x0F] = value;
+    }
+}
+
+//which memory bank controller (if any) the cartridge's ROM writes are routed through
+enum MapperKind {
+    None,
+    Mbc1,
+    Mbc3,
+}
+
+struct Cartridge {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    mapper: MapperKind,
+    has_battery: bool,
+    ram_enabled: bool,
+    //MBC1 splits the ROM bank number across two registers; MBC3 uses rom_bank_low alone
+    rom_bank_low: u8,
+    rom_bank_high: u8,
+    ram_bank: u8,
+    //MBC1 only: 0 selects ROM banking mode (RAM bank is always 0), 1 selects RAM
+    //banking mode (rom_bank_high also selects the RAM bank); rom_bank_high feeds the
+    //switchable ROM bank in both modes
+    banking_mode: u8,
+}
+
+impl Cartridge {
+    fn from_rom(rom: &[u8]) -> Cartridge {
+        let cartridge_type = *rom.get(0x0147).unwrap_or(&0x00);
+        let ram_size_code = *rom.get(0x0149).unwrap_or(&0x00);
+
+        let (mapper, has_battery) = match cartridge_type {
+            0x00 => (MapperKind::None, false),
+            0x01 | 0x02 => (MapperKind::Mbc1, false),
+            0x03 => (MapperKind::Mbc1, true),
+            0x0F | 0x10 | 0x13 => (MapperKind::Mbc3, true),
+            0x11 | 0x12 => (MapperKind::Mbc3, false),
+            _ => (MapperKind::None, false),
+        };
+
+        let ram_bytes: usize = match ram_size_code {
+            0x00 => 0,
+            0x01 => 0x800,   //2 KiB
+            0x02 => 0x2000,  //8 KiB, 1 bank
+            0x03 => 0x8000,  //32 KiB, 4 banks
+            0x04 => 0x20000, //128 KiB, 16 banks
+            0x05 => 0x10000, //64 KiB, 8 banks
+            _ => 0,
+        };
+
+        Cartridge {
+            rom: rom.to_vec(),
+            ram: vec![0; ram_bytes],
+            mapper,
+            has_battery,
+            ram_enabled: false,
+            rom_bank_low: 0,
+            rom_bank_high: 0,
+            ram_bank: 0,
+            banking_mode: 0,
+        }
+    }
+
+    fn current_rom_bank(&self) -> usize {
+        match self.mapper {
+            MapperKind::None => 1,
+            MapperKind::Mbc1 => {
+                let low = if self.rom_bank_low == 0 { 1 } else { self.rom_bank_low as usize };
+                //rom_bank_high feeds bits 5-6 of the switchable region regardless of
+                //banking mode; only the RAM bank is mode-gated, and 0x0000-0x3FFF always
+                //reads ROM bank 0
+                low | ((self.rom_bank_high as usize) << 5)
+            }
+            MapperKind::Mbc3 => {
+                if self.rom_bank_low == 0 { 1 } else { self.rom_bank_low as usize }
+            }
+        }
+    }
+
+    fn current_ram_bank(&self) -> usize {
+        match self.mapper {
+            MapperKind::None => 0,
+            MapperKind::Mbc1 => if self.banking_mode == 1 { self.rom_bank_high as usize } else { 0 },
+            MapperKind::Mbc3 => self.ram_bank as usize,
+        }
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        let offset = match address {
+            0x0000..=0x3FFF => address as usize,
+            _ => self.current_rom_bank() * 0x4000 + (address as usize - 0x4000),
+        };
+        self.rom.get(offset).copied().unwrap_or(0xFF)
+    }
+
+    fn write_control(&mut self, address: u16, value: u8) {
+        match self.mapper {
+            MapperKind::None => {} //no banking registers on a ROM-only cartridge
+            MapperKind::Mbc1 => match address {
+                0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+                0x2000..=0x3FFF => self.rom_bank_low = value & 0x1F,
+                0x4000..=0x5FFF => self.rom_bank_high = value & 0x03,
+                0x6000..=0x7FFF => self.banking_mode = value & 0x01,
+                _ => unreachable!("write_control only sees 0x0000-0x7FFF"),
+            },
+            MapperKind::Mbc3 => match address {
+                0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+                0x2000..=0x3FFF => self.rom_bank_low = value & 0x7F,
+                //0x08-0x0C here would select an RTC register instead of a RAM bank;
+                //the RTC itself isn't modeled
+                0x4000..=0x5FFF => self.ram_bank = value & 0x03,
+                0x6000..=0x7FFF => {} //RTC latch, not modeled
+                _ => unreachable!("write_control only sees 0x0000-0x7FFF"),
+            },
+        }
+    }
+
+    fn read_ram(&self, offset: u16) -> u8 {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return 0xFF;
+        }
+        let address = self.current_ram_bank() * 0x2000 + offset as usize;
+        self.ram.get(address).copied().unwrap_or(0xFF)
+    }
+
+    fn write_ram(&mut self, offset: u16, value: u8) {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return;
+        }
+        let address = self.current_ram_bank() * 0x2000 + offset as usize;
+        if let Some(byte) = self.ram.get_mut(address) {
+            *byte = value;
+        }
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        if self.has_battery { Some(&self.ram) } else { None }
+    }
+
+    fn load_battery_ram(&mut self, data: &[u8]) {
+        let len = data.len().min(self.ram.len());
+        self.ram[..len].copy_from_slice(&data[..len]);
     }
 }
 
@@ -133,9 +484,9 @@ enum Instruction {
     RRCA(ArithmeticTarget), //(rotate right A register) - bit rotate A register right (not through the carry flag)
     RRLA(ArithmeticTarget), //(rotate right A register) - bit rotate A register right (not through the carry flag)
     CPL(ArithmeticTarget), //(complement) - toggle every bit of the A register
-    BIT(ArithmeticTarget), //(bit test) - test to see if a specific bit of a specific register is set
-    RESET(ArithmeticTarget), //(bit reset) - set a specific bit of a specific register to 0
-    SET(ArithmeticTarget), //(bit set) - set a specific bit of a specific register to 1
+    BIT(ArithmeticTarget, u8), //(bit test) - test to see if a specific bit of a specific register is set
+    RESET(ArithmeticTarget, u8), //(bit reset) - set a specific bit of a specific register to 0
+    SET(ArithmeticTarget, u8), //(bit set) - set a specific bit of a specific register to 1
     SRL(ArithmeticTarget), // (shift right logical) - bit shift a specific register right by 1
     RR(ArithmeticTarget), // (rotate right) - bit rotate a specific register right by 1 through the carry flag
     RL(ArithmeticTarget), //(rotate left) - bit rotate a specific register left by 1 through the carry flag
@@ -143,15 +494,417 @@ enum Instruction {
     RLC(ArithmeticTarget), // (rotate left) - bit rotate a specific register left by 1 (not through the carry flag)
     SRA(ArithmeticTarget), //(shift right arithmetic) - arithmetic shift a specific register right by 1
     SLA(ArithmeticTarget), //(shift left arithmetic) - arithmetic shift a specific register left by 1
-    SWAP(ArithmeticTarget) //(swap nibbles) - switch upper and lower nibble of a specific register
+    SWAP(ArithmeticTarget), //(swap nibbles) - switch upper and lower nibble of a specific register
+    LD(LoadByteTarget, LoadByteSource), //load a byte from source into target
+    NOP, //do nothing for one machine cycle
+    EI, //enable interrupts (takes effect after the following instruction)
+    DI, //disable interrupts immediately
+    RETI, //return from an interrupt handler and re-enable interrupts
+    HALT, //suspend stepping until an interrupt is pending
+    STOP, //like HALT, and additionally halts most of the system's clocks
+    JP(JumpCondition), //absolute jump to a 16-bit immediate address
+    JPHL, //absolute jump to the address in HL
+    JR(JumpCondition), //relative jump by a signed 8-bit offset
+    CALL(JumpCondition), //push the return address and jump to a 16-bit immediate address
+    RET(JumpCondition), //pop a return address pushed by CALL and jump to it
+    RST(u16), //push the return address and jump to one of the eight fixed page-0 vectors
+    PUSH(StackTarget), //push a 16-bit register pair onto the stack
+    POP(StackTarget), //pop a 16-bit register pair off of the stack
+    DAA, //adjust A into valid BCD after an add/subtract
 }
 
+#[derive(Clone, Copy)]
 enum ArithmeticTarget {
-    A,B,C,D,E,H,L,BC,DE,HL,SP
+    A,B,C,D,E,H,L,BC,DE,HL,SP,HLIndirect
+}
+
+//which flag (if any) gates a jump/call/return
+#[derive(Clone, Copy)]
+enum JumpCondition {
+    NotZero,
+    Zero,
+    NotCarry,
+    Carry,
+    Always,
+}
+
+#[derive(Clone, Copy)]
+enum StackTarget {
+    AF,BC,DE,HL
 }
 
-impl CPU {
-    fn execute (&mut self, instruction: Instruction) {
+#[derive(Clone, Copy)]
+enum LoadByteTarget {
+    A,B,C,D,E,H,L,HLIndirect
+}
+
+#[derive(Clone, Copy)]
+enum LoadByteSource {
+    A,B,C,D,E,H,L,HLIndirect
+}
+
+impl Instruction {
+    //turn a raw opcode byte into an Instruction. `prefixed` is true when this byte
+    //followed a 0xCB prefix byte, which selects the bit-rotate/shift/BIT/RES/SET table.
+    fn from_byte(byte: u8, prefixed: bool) -> Option<Instruction> {
+        if prefixed {
+            Instruction::from_byte_prefixed(byte)
+        } else {
+            Instruction::from_byte_not_prefixed(byte)
+        }
+    }
+
+    //the CB-prefixed table: the low 3 bits pick the target register/(HL), and for the
+    //rotate/shift group the next 3 bits pick the operation; for BIT/RES/SET they pick
+    //the bit index instead.
+    fn from_byte_prefixed(byte: u8) -> Option<Instruction> {
+        let target = register_code_to_target(byte);
+        let group = (byte >> 6) & 0x3;
+        let bit = (byte >> 3) & 0x7;
+
+        match group {
+            0b00 => match bit {
+                0 => Some(Instruction::RLC(target)),
+                1 => Some(Instruction::RRC(target)),
+                2 => Some(Instruction::RL(target)),
+                3 => Some(Instruction::RR(target)),
+                4 => Some(Instruction::SLA(target)),
+                5 => Some(Instruction::SRA(target)),
+                6 => Some(Instruction::SWAP(target)),
+                7 => Some(Instruction::SRL(target)),
+                _ => None,
+            },
+            0b01 => Some(Instruction::BIT(target, bit)),
+            0b10 => Some(Instruction::RESET(target, bit)),
+            0b11 => Some(Instruction::SET(target, bit)),
+            _ => None,
+        }
+    }
+
+    //the un-prefixed table. `from_byte` only ever sees the opcode byte itself, so
+    //JP/JR/CALL read their immediate operands from memory at execute time.
+    fn from_byte_not_prefixed(byte: u8) -> Option<Instruction> {
+        match byte {
+            0x00 => Some(Instruction::NOP),
+            0x10 => Some(Instruction::STOP),
+            0x76 => Some(Instruction::HALT),
+            0xF3 => Some(Instruction::DI),
+            0xFB => Some(Instruction::EI),
+            0xD9 => Some(Instruction::RETI),
+
+            0xC3 => Some(Instruction::JP(JumpCondition::Always)),
+            0xC2 => Some(Instruction::JP(JumpCondition::NotZero)),
+            0xCA => Some(Instruction::JP(JumpCondition::Zero)),
+            0xD2 => Some(Instruction::JP(JumpCondition::NotCarry)),
+            0xDA => Some(Instruction::JP(JumpCondition::Carry)),
+            0xE9 => Some(Instruction::JPHL),
+
+            0x18 => Some(Instruction::JR(JumpCondition::Always)),
+            0x20 => Some(Instruction::JR(JumpCondition::NotZero)),
+            0x28 => Some(Instruction::JR(JumpCondition::Zero)),
+            0x30 => Some(Instruction::JR(JumpCondition::NotCarry)),
+            0x38 => Some(Instruction::JR(JumpCondition::Carry)),
+
+            0xCD => Some(Instruction::CALL(JumpCondition::Always)),
+            0xC4 => Some(Instruction::CALL(JumpCondition::NotZero)),
+            0xCC => Some(Instruction::CALL(JumpCondition::Zero)),
+            0xD4 => Some(Instruction::CALL(JumpCondition::NotCarry)),
+            0xDC => Some(Instruction::CALL(JumpCondition::Carry)),
+
+            0xC9 => Some(Instruction::RET(JumpCondition::Always)),
+            0xC0 => Some(Instruction::RET(JumpCondition::NotZero)),
+            0xC8 => Some(Instruction::RET(JumpCondition::Zero)),
+            0xD0 => Some(Instruction::RET(JumpCondition::NotCarry)),
+            0xD8 => Some(Instruction::RET(JumpCondition::Carry)),
+
+            0xC7 => Some(Instruction::RST(0x00)),
+            0xCF => Some(Instruction::RST(0x08)),
+            0xD7 => Some(Instruction::RST(0x10)),
+            0xDF => Some(Instruction::RST(0x18)),
+            0xE7 => Some(Instruction::RST(0x20)),
+            0xEF => Some(Instruction::RST(0x28)),
+            0xF7 => Some(Instruction::RST(0x30)),
+            0xFF => Some(Instruction::RST(0x38)),
+
+            0xC5 => Some(Instruction::PUSH(StackTarget::BC)),
+            0xD5 => Some(Instruction::PUSH(StackTarget::DE)),
+            0xE5 => Some(Instruction::PUSH(StackTarget::HL)),
+            0xF5 => Some(Instruction::PUSH(StackTarget::AF)),
+            0xC1 => Some(Instruction::POP(StackTarget::BC)),
+            0xD1 => Some(Instruction::POP(StackTarget::DE)),
+            0xE1 => Some(Instruction::POP(StackTarget::HL)),
+            0xF1 => Some(Instruction::POP(StackTarget::AF)),
+
+            0x07 => Some(Instruction::RRLA(ArithmeticTarget::A)),
+            0x0F => Some(Instruction::RRCA(ArithmeticTarget::A)),
+            0x17 => Some(Instruction::RLA(ArithmeticTarget::A)),
+            0x1F => Some(Instruction::RRA(ArithmeticTarget::A)),
+            0x27 => Some(Instruction::DAA),
+            0x2F => Some(Instruction::CPL(ArithmeticTarget::A)),
+            0x37 => Some(Instruction::SCF(ArithmeticTarget::A)),
+            0x3F => Some(Instruction::CCF(ArithmeticTarget::A)),
+
+            0x04 => Some(Instruction::INC(ArithmeticTarget::B)),
+            0x0C => Some(Instruction::INC(ArithmeticTarget::C)),
+            0x14 => Some(Instruction::INC(ArithmeticTarget::D)),
+            0x1C => Some(Instruction::INC(ArithmeticTarget::E)),
+            0x24 => Some(Instruction::INC(ArithmeticTarget::H)),
+            0x2C => Some(Instruction::INC(ArithmeticTarget::L)),
+            0x34 => Some(Instruction::INC(ArithmeticTarget::HLIndirect)),
+            0x3C => Some(Instruction::INC(ArithmeticTarget::A)),
+
+            0x05 => Some(Instruction::DEC(ArithmeticTarget::B)),
+            0x0D => Some(Instruction::DEC(ArithmeticTarget::C)),
+            0x15 => Some(Instruction::DEC(ArithmeticTarget::D)),
+            0x1D => Some(Instruction::DEC(ArithmeticTarget::E)),
+            0x25 => Some(Instruction::DEC(ArithmeticTarget::H)),
+            0x2D => Some(Instruction::DEC(ArithmeticTarget::L)),
+            0x35 => Some(Instruction::DEC(ArithmeticTarget::HLIndirect)),
+            0x3D => Some(Instruction::DEC(ArithmeticTarget::A)),
+
+            0x03 => Some(Instruction::INC(ArithmeticTarget::BC)),
+            0x13 => Some(Instruction::INC(ArithmeticTarget::DE)),
+            0x23 => Some(Instruction::INC(ArithmeticTarget::HL)),
+            0x33 => Some(Instruction::INC(ArithmeticTarget::SP)),
+
+            0x0B => Some(Instruction::DEC(ArithmeticTarget::BC)),
+            0x1B => Some(Instruction::DEC(ArithmeticTarget::DE)),
+            0x2B => Some(Instruction::DEC(ArithmeticTarget::HL)),
+            0x3B => Some(Instruction::DEC(ArithmeticTarget::SP)),
+
+            0x09 => Some(Instruction::ADDHL(ArithmeticTarget::BC)),
+            0x19 => Some(Instruction::ADDHL(ArithmeticTarget::DE)),
+            0x29 => Some(Instruction::ADDHL(ArithmeticTarget::HL)),
+            0x39 => Some(Instruction::ADDHL(ArithmeticTarget::SP)),
+
+            0x80..=0x87 => Some(Instruction::ADD(register_code_to_target(byte))),
+            0x88..=0x8F => Some(Instruction::ADC(register_code_to_target(byte))),
+            0x90..=0x97 => Some(Instruction::SUBTRACT(register_code_to_target(byte))),
+            0x98..=0x9F => Some(Instruction::SBC(register_code_to_target(byte))),
+            0xA0..=0xA7 => Some(Instruction::AND(register_code_to_target(byte))),
+            0xA8..=0xAF => Some(Instruction::XOR(register_code_to_target(byte))),
+            0xB0..=0xB7 => Some(Instruction::OR(register_code_to_target(byte))),
+            0xB8..=0xBF => Some(Instruction::CP(register_code_to_target(byte))),
+
+            //0x76 is HALT (mapped above), not LD (HL),(HL)
+            0x40..=0x7F if byte != 0x76 => Some(Instruction::LD(
+                register_code_to_load_target(byte >> 3),
+                register_code_to_load_source(byte),
+            )),
+
+            _ => None,
+        }
+    }
+}
+
+//shared register-selection decoding: the low 3 bits of an opcode (B,C,D,E,H,L,(HL),A)
+//name a register or the byte pointed to by HL, in that fixed order, across several
+//instruction groups (ALU ops, LD r,r', and the whole CB-prefixed table).
+fn register_code_to_target(byte: u8) -> ArithmeticTarget {
+    match byte & 0x7 {
+        0 => ArithmeticTarget::B,
+        1 => ArithmeticTarget::C,
+        2 => ArithmeticTarget::D,
+        3 => ArithmeticTarget::E,
+        4 => ArithmeticTarget::H,
+        5 => ArithmeticTarget::L,
+        6 => ArithmeticTarget::HLIndirect,
+        _ => ArithmeticTarget::A,
+    }
+}
+
+fn register_code_to_load_target(code: u8) -> LoadByteTarget {
+    match code & 0x7 {
+        0 => LoadByteTarget::B,
+        1 => LoadByteTarget::C,
+        2 => LoadByteTarget::D,
+        3 => LoadByteTarget::E,
+        4 => LoadByteTarget::H,
+        5 => LoadByteTarget::L,
+        6 => LoadByteTarget::HLIndirect,
+        _ => LoadByteTarget::A,
+    }
+}
+
+fn register_code_to_load_source(byte: u8) -> LoadByteSource {
+    match byte & 0x7 {
+        0 => LoadByteSource::B,
+        1 => LoadByteSource::C,
+        2 => LoadByteSource::D,
+        3 => LoadByteSource::E,
+        4 => LoadByteSource::H,
+        5 => LoadByteSource::L,
+        6 => LoadByteSource::HLIndirect,
+        _ => LoadByteSource::A,
+    }
+}
+
+impl<M: Model> CPU<M> {
+    fn new(bus: MemoryBus, _model: M) -> CPU<M> {
+        CPU {
+            registers: M::initial_registers(),
+            pc: 0x0100,
+            bus,
+            clock: 0,
+            ime: false,
+            ei_pending: false,
+            halted: false,
+            stopped: false,
+            breakpoints: Vec::new(),
+            double_speed: false,
+            model: std::marker::PhantomData,
+        }
+    }
+
+    //build a CPU and its memory bus together from a ROM image. `M::IS_CGB` is the
+    //single source of truth for whether the bus's CGB-only bank-switch registers
+    //are live, so callers no longer pass a separate `cgb` flag to `MemoryBus::load_rom`
+    //that could disagree with the model they picked.
+    fn load_rom(rom: &[u8], model: M) -> CPU<M> {
+        let bus = MemoryBus::load_rom(rom, M::IS_CGB);
+        CPU::new(bus, model)
+    }
+
+    //fetch the opcode at `pc`, decode it (re-reading a second byte when it's the 0xCB
+    //prefix), execute it, land `pc` on the following instruction, and report the
+    //number of T-cycles it consumed (added to the CPU's running clock).
+    //
+    //In CGB double-speed mode the CPU runs instructions at twice the rate, so the
+    //same instruction consumes half as many *real-time* cycles; halve what's
+    //reported here rather than threading a divisor through every call site.
+    fn step(&mut self) -> u8 {
+        if let Some(cycles) = self.handle_interrupts() {
+            let cycles = self.scale_for_speed(cycles);
+            self.clock = self.clock.wrapping_add(cycles as u64);
+            return cycles;
+        }
+
+        //EI's effect is delayed by one instruction: apply it only after this step's
+        //handle_interrupts() has already run, so the instruction immediately after EI
+        //always executes before any interrupt can be dispatched
+        if self.ei_pending {
+            self.ei_pending = false;
+            self.ime = true;
+        }
+
+        if self.halted || self.stopped {
+            if self.bus.interrupt_enable & self.bus.interrupt_flag() & 0x1F != 0 {
+                self.halted = false;
+                self.stopped = false;
+            } else {
+                let cycles = self.scale_for_speed(4);
+                self.clock = self.clock.wrapping_add(cycles as u64);
+                return cycles;
+            }
+        }
+
+        let mut instruction_byte = self.bus.read_byte(self.pc);
+        let prefixed = instruction_byte == 0xCB;
+        if prefixed {
+            instruction_byte = self.bus.read_byte(self.pc.wrapping_add(1));
+        }
+
+        let instruction = if let Some(instruction) = Instruction::from_byte(instruction_byte, prefixed) {
+            instruction
+        } else {
+            let description = format!("0x{}{:02x}", if prefixed { "cb" } else { "" }, instruction_byte);
+            panic!("Unimplemented opcode: {}", description);
+        };
+
+        let branch_taken = self.would_branch(&instruction);
+        let cycles = self.scale_for_speed(instruction_cycles(&instruction, branch_taken));
+        self.pc = self.execute(instruction);
+        self.clock = self.clock.wrapping_add(cycles as u64);
+        cycles
+    }
+
+    //halve the reported cycle count while double-speed mode is active
+    fn scale_for_speed(&self, cycles: u8) -> u8 {
+        if self.double_speed {
+            cycles / 2
+        } else {
+            cycles
+        }
+    }
+
+    //if IME is set and an enabled+requested interrupt exists, push `pc`, clear the
+    //IF bit, and jump to the fixed vector for the highest-priority one pending
+    fn handle_interrupts(&mut self) -> Option<u8> {
+        if !self.ime {
+            return None;
+        }
+
+        let pending = self.bus.interrupt_enable & self.bus.interrupt_flag() & 0x1F;
+        if pending == 0 {
+            return None;
+        }
+
+        let bit = pending.trailing_zeros() as usize;
+        self.ime = false;
+        self.halted = false;
+        self.stopped = false;
+        let flags = self.bus.interrupt_flag();
+        self.bus.set_interrupt_flag(flags & !(1 << bit));
+        self.push_stack(self.pc);
+        self.pc = INTERRUPT_VECTORS[bit];
+
+        //2 machine cycles to decide + 2 to push pc + 1 to jump = 5 machine cycles (20 T)
+        Some(20)
+    }
+
+    fn push_stack(&mut self, value: u16) {
+        self.registers.sp = self.registers.sp.wrapping_sub(1);
+        self.bus.write_byte(self.registers.sp, (value >> 8) as u8);
+        self.registers.sp = self.registers.sp.wrapping_sub(1);
+        self.bus.write_byte(self.registers.sp, (value & 0xFF) as u8);
+    }
+
+    fn pop_stack(&mut self) -> u16 {
+        let low = self.bus.read_byte(self.registers.sp) as u16;
+        self.registers.sp = self.registers.sp.wrapping_add(1);
+        let high = self.bus.read_byte(self.registers.sp) as u16;
+        self.registers.sp = self.registers.sp.wrapping_add(1);
+        (high << 8) | low
+    }
+
+    fn read_next_byte(&self) -> u8 {
+        self.bus.read_byte(self.pc.wrapping_add(1))
+    }
+
+    fn read_next_word(&self) -> u16 {
+        let low = self.bus.read_byte(self.pc.wrapping_add(1)) as u16;
+        let high = self.bus.read_byte(self.pc.wrapping_add(2)) as u16;
+        (high << 8) | low
+    }
+
+    fn test_jump_condition(&self, condition: JumpCondition) -> bool {
+        match condition {
+            JumpCondition::NotZero => !self.registers.f.zero,
+            JumpCondition::Zero => self.registers.f.zero,
+            JumpCondition::NotCarry => !self.registers.f.carry,
+            JumpCondition::Carry => self.registers.f.carry,
+            JumpCondition::Always => true,
+        }
+    }
+
+    //whether a JP/JR/CALL/RET would branch, evaluated against the flags as they stand
+    //before `execute` runs - needed up front to look up the right cycle cost
+    fn would_branch(&self, instruction: &Instruction) -> bool {
+        match instruction {
+            Instruction::JP(condition)
+            | Instruction::JR(condition)
+            | Instruction::CALL(condition)
+            | Instruction::RET(condition) => self.test_jump_condition(*condition),
+            _ => false,
+        }
+    }
+
+    fn execute (&mut self, instruction: Instruction) -> u16 {
+        //control-flow instructions return their own next pc below; every other
+        //instruction is a single byte, except the CB-prefixed bit-ops, which are two
+        //(the 0xCB byte plus the op byte)
+        let prefixed = instruction_is_prefixed(&instruction);
+
         match instruction {
             Instruction::ADD(target) =>{
                 match target {
@@ -190,7 +943,14 @@ impl CPU {
                         let new_value = self.add(value);
                         self.registers.a = new_value;
                     }
-                    //more targets
+                    ArithmeticTarget::HLIndirect => {
+                        let value = self.bus.read_byte(self.registers.get_hl());
+                        let new_value = self.add(value);
+                        self.registers.a = new_value;
+                    }
+                    ArithmeticTarget::BC | ArithmeticTarget::DE | ArithmeticTarget::HL | ArithmeticTarget::SP => {
+                        unreachable!("ADD does not take a 16-bit target")
+                    }
                 }
 
             }
@@ -216,21 +976,289 @@ impl CPU {
                         let new_value = self.addhl(value);
                         self.registers.set_hl(new_value);
                     }
-                    
-                    //more targets
+
+                    _ => unreachable!("ADDHL only takes a 16-bit target"),
                 }
                 //more instructions
             }
+            Instruction::ADC(target) => {
+                let value = self.read_target(target);
+                let new_value = self.adc(value);
+                self.registers.a = new_value;
+            }
+            Instruction::SUBTRACT(target) => {
+                let value = self.read_target(target);
+                let new_value = self.sub(value);
+                self.registers.a = new_value;
+            }
+            Instruction::SBC(target) => {
+                let value = self.read_target(target);
+                let new_value = self.sbc(value);
+                self.registers.a = new_value;
+            }
+            Instruction::AND(target) => {
+                let value = self.read_target(target);
+                let new_value = self.and(value);
+                self.registers.a = new_value;
+            }
+            Instruction::OR(target) => {
+                let value = self.read_target(target);
+                let new_value = self.or(value);
+                self.registers.a = new_value;
+            }
+            Instruction::XOR(target) => {
+                let value = self.read_target(target);
+                let new_value = self.xor(value);
+                self.registers.a = new_value;
+            }
+            Instruction::CP(target) => {
+                let value = self.read_target(target);
+                self.cp(value);
+            }
+            Instruction::INC(target) => match target {
+                ArithmeticTarget::BC => self.registers.set_bc(self.registers.get_bc().wrapping_add(1)),
+                ArithmeticTarget::DE => self.registers.set_de(self.registers.get_de().wrapping_add(1)),
+                ArithmeticTarget::HL => self.registers.set_hl(self.registers.get_hl().wrapping_add(1)),
+                ArithmeticTarget::SP => self.registers.sp = self.registers.sp.wrapping_add(1),
+                target => {
+                    let value = self.read_target(target);
+                    let new_value = self.inc(value);
+                    self.write_target(target, new_value);
+                }
+            },
+            Instruction::DEC(target) => match target {
+                ArithmeticTarget::BC => self.registers.set_bc(self.registers.get_bc().wrapping_sub(1)),
+                ArithmeticTarget::DE => self.registers.set_de(self.registers.get_de().wrapping_sub(1)),
+                ArithmeticTarget::HL => self.registers.set_hl(self.registers.get_hl().wrapping_sub(1)),
+                ArithmeticTarget::SP => self.registers.sp = self.registers.sp.wrapping_sub(1),
+                target => {
+                    let value = self.read_target(target);
+                    let new_value = self.dec(value);
+                    self.write_target(target, new_value);
+                }
+            },
+            Instruction::CCF(_) => self.ccf(),
+            Instruction::SCF(_) => self.scf(),
+            Instruction::CPL(_) => self.cpl(),
+            Instruction::RRLA(_) => {
+                //0x07 - RLCA: rotate A left, not through the carry flag
+                let value = self.registers.a;
+                self.registers.a = self.rotate_left(value, false);
+            }
+            Instruction::RLA(_) => {
+                let value = self.registers.a;
+                self.registers.a = self.rotate_left_through_carry(value, false);
+            }
+            Instruction::RRCA(_) => {
+                let value = self.registers.a;
+                self.registers.a = self.rotate_right(value, false);
+            }
+            Instruction::RRA(_) => {
+                let value = self.registers.a;
+                self.registers.a = self.rotate_right_through_carry(value, false);
+            }
+            Instruction::BIT(target, bit) => {
+                let value = self.read_target(target);
+                self.bit(value, bit);
+            }
+            Instruction::RESET(target, bit) => {
+                let value = self.read_target(target);
+                self.write_target(target, value & !(1 << bit));
+            }
+            Instruction::SET(target, bit) => {
+                let value = self.read_target(target);
+                self.write_target(target, value | (1 << bit));
+            }
+            Instruction::SRL(target) => {
+                let value = self.read_target(target);
+                let new_value = self.srl(value);
+                self.write_target(target, new_value);
+            }
+            Instruction::RR(target) => {
+                let value = self.read_target(target);
+                let new_value = self.rotate_right_through_carry(value, true);
+                self.write_target(target, new_value);
+            }
+            Instruction::RL(target) => {
+                let value = self.read_target(target);
+                let new_value = self.rotate_left_through_carry(value, true);
+                self.write_target(target, new_value);
+            }
+            Instruction::RRC(target) => {
+                let value = self.read_target(target);
+                let new_value = self.rotate_right(value, true);
+                self.write_target(target, new_value);
+            }
+            Instruction::RLC(target) => {
+                let value = self.read_target(target);
+                let new_value = self.rotate_left(value, true);
+                self.write_target(target, new_value);
+            }
+            Instruction::SRA(target) => {
+                let value = self.read_target(target);
+                let new_value = self.sra(value);
+                self.write_target(target, new_value);
+            }
+            Instruction::SLA(target) => {
+                let value = self.read_target(target);
+                let new_value = self.sla(value);
+                self.write_target(target, new_value);
+            }
+            Instruction::SWAP(target) => {
+                let value = self.read_target(target);
+                let new_value = self.swap(value);
+                self.write_target(target, new_value);
+            }
+            Instruction::LD(target, source) => {
+                let value = match source {
+                    LoadByteSource::A => self.registers.a,
+                    LoadByteSource::B => self.registers.b,
+                    LoadByteSource::C => self.registers.c,
+                    LoadByteSource::D => self.registers.d,
+                    LoadByteSource::E => self.registers.e,
+                    LoadByteSource::H => self.registers.h,
+                    LoadByteSource::L => self.registers.l,
+                    LoadByteSource::HLIndirect => self.bus.read_byte(self.registers.get_hl()),
+                };
+                match target {
+                    LoadByteTarget::A => self.registers.a = value,
+                    LoadByteTarget::B => self.registers.b = value,
+                    LoadByteTarget::C => self.registers.c = value,
+                    LoadByteTarget::D => self.registers.d = value,
+                    LoadByteTarget::E => self.registers.e = value,
+                    LoadByteTarget::H => self.registers.h = value,
+                    LoadByteTarget::L => self.registers.l = value,
+                    LoadByteTarget::HLIndirect => {
+                        let address = self.registers.get_hl();
+                        self.bus.write_byte(address, value);
+                    }
+                };
+            }
+            Instruction::NOP => {}
+            Instruction::DI => self.ime = false,
+            Instruction::EI => self.ei_pending = true,
+            Instruction::RETI => {
+                self.ime = true;
+                return self.pop_stack();
+            }
+            Instruction::HALT => self.halted = true,
+            //a STOP with KEY1 armed performs the speed switch instead of actually
+            //stopping; otherwise it suspends stepping like HALT
+            Instruction::STOP => {
+                if self.bus.speed_switch_armed() {
+                    self.double_speed = self.bus.complete_speed_switch();
+                } else {
+                    self.stopped = true;
+                }
+            }
+
+            Instruction::JP(condition) => {
+                return if self.test_jump_condition(condition) {
+                    self.read_next_word()
+                } else {
+                    self.pc.wrapping_add(3)
+                };
+            }
+            Instruction::JPHL => return self.registers.get_hl(),
+            Instruction::JR(condition) => {
+                let offset = self.read_next_byte() as i8;
+                let next_pc = self.pc.wrapping_add(2);
+                return if self.test_jump_condition(condition) {
+                    next_pc.wrapping_add(offset as i16 as u16)
+                } else {
+                    next_pc
+                };
+            }
+            Instruction::CALL(condition) => {
+                let next_pc = self.pc.wrapping_add(3);
+                return if self.test_jump_condition(condition) {
+                    self.push_stack(next_pc);
+                    self.read_next_word()
+                } else {
+                    next_pc
+                };
+            }
+            Instruction::RET(condition) => {
+                return if self.test_jump_condition(condition) {
+                    self.pop_stack()
+                } else {
+                    self.pc.wrapping_add(1)
+                };
+            }
+            Instruction::RST(address) => {
+                let next_pc = self.pc.wrapping_add(1);
+                self.push_stack(next_pc);
+                return address;
+            }
+            Instruction::PUSH(target) => {
+                let value = match target {
+                    StackTarget::AF => self.registers.get_af(),
+                    StackTarget::BC => self.registers.get_bc(),
+                    StackTarget::DE => self.registers.get_de(),
+                    StackTarget::HL => self.registers.get_hl(),
+                };
+                self.push_stack(value);
+            }
+            Instruction::POP(target) => {
+                let value = self.pop_stack();
+                match target {
+                    StackTarget::AF => self.registers.set_af(value),
+                    StackTarget::BC => self.registers.set_bc(value),
+                    StackTarget::DE => self.registers.set_de(value),
+                    StackTarget::HL => self.registers.set_hl(value),
+                };
+            }
+            Instruction::DAA => self.daa(),
+        }
+
+        if prefixed {
+            self.pc.wrapping_add(2)
+        } else {
+            self.pc.wrapping_add(1)
+        }
+    }
+
+    fn read_target(&self, target: ArithmeticTarget) -> u8 {
+        match target {
+            ArithmeticTarget::A => self.registers.a,
+            ArithmeticTarget::B => self.registers.b,
+            ArithmeticTarget::C => self.registers.c,
+            ArithmeticTarget::D => self.registers.d,
+            ArithmeticTarget::E => self.registers.e,
+            ArithmeticTarget::H => self.registers.h,
+            ArithmeticTarget::L => self.registers.l,
+            ArithmeticTarget::HLIndirect => self.bus.read_byte(self.registers.get_hl()),
+            ArithmeticTarget::BC | ArithmeticTarget::DE | ArithmeticTarget::HL | ArithmeticTarget::SP => {
+                unreachable!("16-bit target used where an 8-bit value was expected")
+            }
+        }
+    }
+
+    fn write_target(&mut self, target: ArithmeticTarget, value: u8) {
+        match target {
+            ArithmeticTarget::A => self.registers.a = value,
+            ArithmeticTarget::B => self.registers.b = value,
+            ArithmeticTarget::C => self.registers.c = value,
+            ArithmeticTarget::D => self.registers.d = value,
+            ArithmeticTarget::E => self.registers.e = value,
+            ArithmeticTarget::H => self.registers.h = value,
+            ArithmeticTarget::L => self.registers.l = value,
+            ArithmeticTarget::HLIndirect => {
+                let address = self.registers.get_hl();
+                self.bus.write_byte(address, value);
+            }
+            ArithmeticTarget::BC | ArithmeticTarget::DE | ArithmeticTarget::HL | ArithmeticTarget::SP => {
+                unreachable!("16-bit target used where an 8-bit value was expected")
+            }
         }
     }
-    
 
     fn add(&mut self, value: u8) -> u8{
         let (new_value, did_overflow) = self.registers.a.overflowing_add(value);
         self.registers.f.zero = new_value == 0;
         self.registers.f.subtract = false;
         self.registers.f.carry = did_overflow;
-        self.registers.f.half_carry = (self.registers.a & 0xF) + (value & 0xF) > 0xF;
+        self.registers.f.half_carry = half_carry_on_add(self.registers.a, value, 0);
         new_value
     }
 
@@ -238,9 +1266,431 @@ impl CPU {
         let (new_value, did_overflow) = self.registers.get_hl().overflowing_add(value);
         self.registers.f.subtract = false;
         self.registers.f.carry = did_overflow;
-        self.registers.f.half_carry = (self.registers.get_hl() & 0xF) + (value & 0xF) > 0xF; //TODO look this up
+        //16-bit half-carry is out of bit 11, not bit 3
+        self.registers.f.half_carry = (self.registers.get_hl() & 0x0FFF) + (value & 0x0FFF) > 0x0FFF;
         new_value
     }
+
+    fn adc(&mut self, value: u8) -> u8 {
+        let carry = if self.registers.f.carry { 1 } else { 0 };
+        let (partial, overflow1) = self.registers.a.overflowing_add(value);
+        let (new_value, overflow2) = partial.overflowing_add(carry);
+        self.registers.f.zero = new_value == 0;
+        self.registers.f.subtract = false;
+        self.registers.f.carry = overflow1 || overflow2;
+        self.registers.f.half_carry = half_carry_on_add(self.registers.a, value, carry);
+        new_value
+    }
+
+    fn sub(&mut self, value: u8) -> u8 {
+        let (new_value, did_overflow) = self.registers.a.overflowing_sub(value);
+        self.registers.f.zero = new_value == 0;
+        self.registers.f.subtract = true;
+        self.registers.f.carry = did_overflow;
+        self.registers.f.half_carry = half_carry_on_sub(self.registers.a, value, 0);
+        new_value
+    }
+
+    fn sbc(&mut self, value: u8) -> u8 {
+        let carry = if self.registers.f.carry { 1 } else { 0 };
+        let (partial, overflow1) = self.registers.a.overflowing_sub(value);
+        let (new_value, overflow2) = partial.overflowing_sub(carry);
+        self.registers.f.zero = new_value == 0;
+        self.registers.f.subtract = true;
+        self.registers.f.carry = overflow1 || overflow2;
+        self.registers.f.half_carry = half_carry_on_sub(self.registers.a, value, carry);
+        new_value
+    }
+
+    fn and(&mut self, value: u8) -> u8 {
+        let new_value = self.registers.a & value;
+        self.registers.f.zero = new_value == 0;
+        self.registers.f.subtract = false;
+        self.registers.f.half_carry = true;
+        self.registers.f.carry = false;
+        new_value
+    }
+
+    fn or(&mut self, value: u8) -> u8 {
+        let new_value = self.registers.a | value;
+        self.registers.f.zero = new_value == 0;
+        self.registers.f.subtract = false;
+        self.registers.f.half_carry = false;
+        self.registers.f.carry = false;
+        new_value
+    }
+
+    fn xor(&mut self, value: u8) -> u8 {
+        let new_value = self.registers.a ^ value;
+        self.registers.f.zero = new_value == 0;
+        self.registers.f.subtract = false;
+        self.registers.f.half_carry = false;
+        self.registers.f.carry = false;
+        new_value
+    }
+
+    fn cp(&mut self, value: u8) {
+        let (new_value, did_overflow) = self.registers.a.overflowing_sub(value);
+        self.registers.f.zero = new_value == 0;
+        self.registers.f.subtract = true;
+        self.registers.f.carry = did_overflow;
+        self.registers.f.half_carry = half_carry_on_sub(self.registers.a, value, 0);
+    }
+
+    fn inc(&mut self, value: u8) -> u8 {
+        let new_value = value.wrapping_add(1);
+        self.registers.f.zero = new_value == 0;
+        self.registers.f.subtract = false;
+        self.registers.f.half_carry = half_carry_on_add(value, 1, 0);
+        new_value
+    }
+
+    fn dec(&mut self, value: u8) -> u8 {
+        let new_value = value.wrapping_sub(1);
+        self.registers.f.zero = new_value == 0;
+        self.registers.f.subtract = true;
+        self.registers.f.half_carry = half_carry_on_sub(value, 1, 0);
+        new_value
+    }
+
+    //BCD-correct A after an add/sub, the way hardware requires before/after 8-bit
+    //decimal arithmetic
+    fn daa(&mut self) {
+        let mut adjustment: u8 = 0;
+        let mut carry = self.registers.f.carry;
+
+        if !self.registers.f.subtract {
+            if self.registers.f.half_carry || (self.registers.a & 0x0F) > 0x09 {
+                adjustment |= 0x06;
+            }
+            if self.registers.f.carry || self.registers.a > 0x99 {
+                adjustment |= 0x60;
+                carry = true;
+            }
+            self.registers.a = self.registers.a.wrapping_add(adjustment);
+        } else {
+            if self.registers.f.half_carry {
+                adjustment |= 0x06;
+            }
+            if self.registers.f.carry {
+                adjustment |= 0x60;
+            }
+            self.registers.a = self.registers.a.wrapping_sub(adjustment);
+        }
+
+        self.registers.f.zero = self.registers.a == 0;
+        self.registers.f.half_carry = false;
+        self.registers.f.carry = carry;
+    }
+
+    fn ccf(&mut self) {
+        self.registers.f.subtract = false;
+        self.registers.f.half_carry = false;
+        self.registers.f.carry = !self.registers.f.carry;
+    }
+
+    fn scf(&mut self) {
+        self.registers.f.subtract = false;
+        self.registers.f.half_carry = false;
+        self.registers.f.carry = true;
+    }
+
+    fn cpl(&mut self) {
+        self.registers.a = !self.registers.a;
+        self.registers.f.subtract = true;
+        self.registers.f.half_carry = true;
+    }
+
+    fn bit(&mut self, value: u8, bit: u8) {
+        self.registers.f.zero = (value >> bit) & 0x1 == 0;
+        self.registers.f.subtract = false;
+        self.registers.f.half_carry = true;
+    }
+
+    //rotate left, carry flag <- bit 7, bit 0 <- bit 7 (not through the carry flag)
+    fn rotate_left(&mut self, value: u8, set_zero: bool) -> u8 {
+        let new_value = value.rotate_left(1);
+        self.registers.f.carry = value & 0x80 != 0;
+        self.registers.f.subtract = false;
+        self.registers.f.half_carry = false;
+        self.registers.f.zero = set_zero && new_value == 0;
+        new_value
+    }
+
+    //rotate left through the carry flag: carry flag <- bit 7, bit 0 <- old carry flag
+    fn rotate_left_through_carry(&mut self, value: u8, set_zero: bool) -> u8 {
+        let carry_in = if self.registers.f.carry { 1 } else { 0 };
+        let new_value = (value << 1) | carry_in;
+        self.registers.f.carry = value & 0x80 != 0;
+        self.registers.f.subtract = false;
+        self.registers.f.half_carry = false;
+        self.registers.f.zero = set_zero && new_value == 0;
+        new_value
+    }
+
+    //rotate right, carry flag <- bit 0, bit 7 <- bit 0 (not through the carry flag)
+    fn rotate_right(&mut self, value: u8, set_zero: bool) -> u8 {
+        let new_value = value.rotate_right(1);
+        self.registers.f.carry = value & 0x1 != 0;
+        self.registers.f.subtract = false;
+        self.registers.f.half_carry = false;
+        self.registers.f.zero = set_zero && new_value == 0;
+        new_value
+    }
+
+    //rotate right through the carry flag: carry flag <- bit 0, bit 7 <- old carry flag
+    fn rotate_right_through_carry(&mut self, value: u8, set_zero: bool) -> u8 {
+        let carry_in = if self.registers.f.carry { 0x80 } else { 0 };
+        let new_value = (value >> 1) | carry_in;
+        self.registers.f.carry = value & 0x1 != 0;
+        self.registers.f.subtract = false;
+        self.registers.f.half_carry = false;
+        self.registers.f.zero = set_zero && new_value == 0;
+        new_value
+    }
+
+    fn sla(&mut self, value: u8) -> u8 {
+        let new_value = value << 1;
+        self.registers.f.carry = value & 0x80 != 0;
+        self.registers.f.subtract = false;
+        self.registers.f.half_carry = false;
+        self.registers.f.zero = new_value == 0;
+        new_value
+    }
+
+    //arithmetic shift right: bit 7 is preserved, unlike a logical shift
+    fn sra(&mut self, value: u8) -> u8 {
+        let new_value = (value >> 1) | (value & 0x80);
+        self.registers.f.carry = value & 0x1 != 0;
+        self.registers.f.subtract = false;
+        self.registers.f.half_carry = false;
+        self.registers.f.zero = new_value == 0;
+        new_value
+    }
+
+    fn srl(&mut self, value: u8) -> u8 {
+        let new_value = value >> 1;
+        self.registers.f.carry = value & 0x1 != 0;
+        self.registers.f.subtract = false;
+        self.registers.f.half_carry = false;
+        self.registers.f.zero = new_value == 0;
+        new_value
+    }
+
+    fn swap(&mut self, value: u8) -> u8 {
+        let new_value = (value << 4) | (value >> 4);
+        self.registers.f.zero = new_value == 0;
+        self.registers.f.subtract = false;
+        self.registers.f.half_carry = false;
+        self.registers.f.carry = false;
+        new_value
+    }
+
+    //step until `pc` lands on a breakpoint, so a front-end can run freely between stops
+    fn run(&mut self) {
+        loop {
+            if self.breakpoints.contains(&self.pc) {
+                return;
+            }
+            self.step();
+        }
+    }
+
+    //print the full register file and decoded flag bits
+    fn dump_state(&self) {
+        println!(
+            "a:{:02x} f:{:02x} b:{:02x} c:{:02x} d:{:02x} e:{:02x} h:{:02x} l:{:02x} sp:{:04x} pc:{:04x}",
+            self.registers.a,
+            u8::from(&self.registers.f),
+            self.registers.b,
+            self.registers.c,
+            self.registers.d,
+            self.registers.e,
+            self.registers.h,
+            self.registers.l,
+            self.registers.sp,
+            self.pc,
+        );
+        println!(
+            "flags: z:{} n:{} h:{} c:{}",
+            self.registers.f.zero as u8,
+            self.registers.f.subtract as u8,
+            self.registers.f.half_carry as u8,
+            self.registers.f.carry as u8,
+        );
+    }
+
+    //run one short textual debugger command, e.g. `b 0x0150`, `s`, `r`, `set a 0x42`.
+    //returns false if the command wasn't recognized.
+    fn execute_command(&mut self, args: &[&str]) -> bool {
+        match args {
+            ["b", addr] => match parse_debugger_u16(addr) {
+                Some(address) => {
+                    match self.breakpoints.iter().position(|&bp| bp == address) {
+                        Some(index) => { self.breakpoints.remove(index); }
+                        None => self.breakpoints.push(address),
+                    }
+                    true
+                }
+                None => false,
+            },
+            ["s"] => {
+                self.step();
+                true
+            }
+            ["r"] => {
+                self.dump_state();
+                true
+            }
+            ["set", target, value] => match parse_debugger_u16(value) {
+                Some(value) => self.poke(target, value),
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    //assign a register, or failing that treat `target` as a memory address, the way
+    //directly assigning a register then resuming would
+    fn poke(&mut self, target: &str, value: u16) -> bool {
+        match target {
+            "a" => self.registers.a = value as u8,
+            "b" => self.registers.b = value as u8,
+            "c" => self.registers.c = value as u8,
+            "d" => self.registers.d = value as u8,
+            "e" => self.registers.e = value as u8,
+            "h" => self.registers.h = value as u8,
+            "l" => self.registers.l = value as u8,
+            "sp" => self.registers.sp = value,
+            "pc" => self.pc = value,
+            "af" => self.registers.set_af(value),
+            "bc" => self.registers.set_bc(value),
+            "de" => self.registers.set_de(value),
+            "hl" => self.registers.set_hl(value),
+            _ => match parse_debugger_u16(target) {
+                Some(address) => self.bus.write_byte(address, value as u8),
+                None => return false,
+            },
+        }
+        true
+    }
+}
+
+//accepts both `0x`-prefixed hex and plain decimal, the way a human typing a quick
+//debugger command would write an address or immediate value
+fn parse_debugger_u16(text: &str) -> Option<u16> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => text.parse::<u16>().ok(),
+    }
+}
+
+fn instruction_is_prefixed(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::BIT(..)
+            | Instruction::RESET(..)
+            | Instruction::SET(..)
+            | Instruction::SRL(_)
+            | Instruction::RR(_)
+            | Instruction::RL(_)
+            | Instruction::RRC(_)
+            | Instruction::RLC(_)
+            | Instruction::SRA(_)
+            | Instruction::SLA(_)
+            | Instruction::SWAP(_)
+    )
+}
+
+//T-cycle cost of an instruction, the way a cycle table drives the rest of an
+//emulator's scheduling. `branch_taken` selects between the taken/not-taken costs of
+//conditional JP/JR/CALL/RET; every other instruction ignores it.
+fn instruction_cycles(instruction: &Instruction, branch_taken: bool) -> u8 {
+    let _ = branch_taken;
+    match instruction {
+        Instruction::ADD(target)
+        | Instruction::ADC(target)
+        | Instruction::SUBTRACT(target)
+        | Instruction::SBC(target)
+        | Instruction::AND(target)
+        | Instruction::OR(target)
+        | Instruction::XOR(target)
+        | Instruction::CP(target) => cycles_for_8bit_target(target),
+
+        Instruction::INC(target) | Instruction::DEC(target) => match target {
+            ArithmeticTarget::BC | ArithmeticTarget::DE | ArithmeticTarget::HL | ArithmeticTarget::SP => 8,
+            ArithmeticTarget::HLIndirect => 12,
+            _ => 4,
+        },
+
+        Instruction::ADDHL(_) => 8,
+
+        Instruction::CCF(_)
+        | Instruction::SCF(_)
+        | Instruction::CPL(_)
+        | Instruction::RRLA(_)
+        | Instruction::RLA(_)
+        | Instruction::RRCA(_)
+        | Instruction::RRA(_)
+        | Instruction::NOP
+        | Instruction::DI
+        | Instruction::EI
+        | Instruction::HALT
+        | Instruction::STOP => 4,
+
+        Instruction::RETI => 16,
+
+        Instruction::JP(JumpCondition::Always) => 16,
+        Instruction::JP(_) => if branch_taken { 16 } else { 12 },
+        Instruction::JPHL => 4,
+        Instruction::JR(JumpCondition::Always) => 12,
+        Instruction::JR(_) => if branch_taken { 12 } else { 8 },
+        Instruction::CALL(JumpCondition::Always) => 24,
+        Instruction::CALL(_) => if branch_taken { 24 } else { 12 },
+        Instruction::RET(JumpCondition::Always) => 16,
+        Instruction::RET(_) => if branch_taken { 20 } else { 8 },
+        Instruction::RST(_) => 16,
+        Instruction::PUSH(_) => 16,
+        Instruction::POP(_) => 12,
+        Instruction::DAA => 4,
+
+        Instruction::LD(target, source) => {
+            if matches!(target, LoadByteTarget::HLIndirect) || matches!(source, LoadByteSource::HLIndirect) {
+                8
+            } else {
+                4
+            }
+        }
+
+        Instruction::BIT(target, _) => cycles_for_cb_target(target, 8, 12),
+        Instruction::RESET(target, _) | Instruction::SET(target, _) => cycles_for_cb_target(target, 8, 16),
+        Instruction::SRL(target)
+        | Instruction::RR(target)
+        | Instruction::RL(target)
+        | Instruction::RRC(target)
+        | Instruction::RLC(target)
+        | Instruction::SRA(target)
+        | Instruction::SLA(target)
+        | Instruction::SWAP(target) => cycles_for_cb_target(target, 8, 16),
+    }
+}
+
+//shared half-carry check for 8-bit adds (with an optional incoming carry, for ADC)
+fn half_carry_on_add(a: u8, b: u8, carry: u8) -> bool {
+    (a & 0xF) + (b & 0xF) + carry > 0xF
+}
+
+//shared half-carry check for 8-bit subtracts (with an optional incoming carry, for SBC)
+fn half_carry_on_sub(a: u8, b: u8, carry: u8) -> bool {
+    (a & 0xF) < (b & 0xF) + carry
+}
+
+fn cycles_for_8bit_target(target: &ArithmeticTarget) -> u8 {
+    if matches!(target, ArithmeticTarget::HLIndirect) { 8 } else { 4 }
+}
+
+fn cycles_for_cb_target(target: &ArithmeticTarget, register_cost: u8, hl_indirect_cost: u8) -> u8 {
+    if matches!(target, ArithmeticTarget::HLIndirect) { hl_indirect_cost } else { register_cost }
 }
 
 fn main() {